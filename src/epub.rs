@@ -1,10 +1,12 @@
 mod render;
 
+pub use render::{Marker, Paragraph};
 use render::{render_node, RenderAttributes};
 
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, bail, Context, Result};
 use roxmltree::{Document, Node};
@@ -14,6 +16,29 @@ pub struct Epub {
     archive: ZipArchive<File>,
     manifest: HashMap<String, String>,
     spine: Vec<String>,
+    pub toc: Vec<(String, usize)>,
+    pub metadata: Metadata,
+}
+
+#[derive(Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub creators: Vec<String>,
+    pub language: Option<String>,
+    pub publisher: Option<String>,
+    pub date: Option<String>,
+    pub identifier: Option<String>,
+}
+
+pub struct Chapter {
+    pub paragraphs: Vec<Paragraph>,
+    /// `src` hrefs, in the order their `[IMG:n]` markers appear.
+    pub images: Vec<String>,
+    /// Link hrefs, in the order their `[n]` markers appear.
+    pub links: Vec<String>,
+    /// `id`/`name` of every element, paired with the index its containing
+    /// paragraph got in `paragraphs`.
+    pub anchors: Vec<(String, usize)>,
 }
 
 struct Container {
@@ -24,6 +49,8 @@ struct Container {
 struct Package {
     pub manifest: HashMap<String, String>,
     pub spine: Vec<String>,
+    pub toc_path: Option<String>,
+    pub metadata: Metadata,
 }
 
 impl Epub {
@@ -43,29 +70,137 @@ impl Epub {
         } = parse_container(&container_xml).context("failed to parse container")?;
 
         let package_xml = read_archive(&mut archive, &package_path)?;
-        let Package { manifest, spine } =
-            parse_package(&package_xml, &base_path).context("failed to parse package")?;
+        let Package {
+            manifest,
+            spine,
+            toc_path,
+            metadata,
+        } = parse_package(&package_xml, &base_path).context("failed to parse package")?;
+
+        let toc = if let Some(toc_path) = toc_path {
+            let toc_xml = read_archive(&mut archive, &toc_path)?;
+            parse_toc(&toc_xml, &toc_path, &manifest, &spine)
+                .with_context(|| format!("failed to parse table of contents '{toc_path}'"))?
+        } else {
+            Vec::new()
+        };
 
-        Ok(Self {
+        let mut epub = Self {
             archive,
             manifest,
             spine,
-        })
+            toc,
+            metadata,
+        };
+
+        if epub.toc.is_empty() {
+            epub.toc = epub.fallback_toc();
+        }
+
+        Ok(epub)
     }
 
     pub fn len(&self) -> usize {
         self.spine.len()
     }
 
-    pub fn render(&mut self, index: usize) -> Result<String> {
+    /// Renders a spine chapter into its logical paragraphs (styled runs,
+    /// not yet wrapped to any particular width) plus the image/link hrefs
+    /// it references. Callers wrap the paragraphs to the terminal width
+    /// with [`crate::ui::wrap`].
+    pub fn chapter(&mut self, index: usize) -> Result<Chapter> {
         let id = &self.spine[index];
-        let path = &self.manifest[id];
-        let xml = read_archive(&mut self.archive, path)?;
+        let path = self.manifest[id].clone();
+        let xml = read_archive(&mut self.archive, &path)?;
         let doc = Document::parse(&xml)?;
 
-        let text = render_node(doc.root(), RenderAttributes::default());
+        let mut images = Vec::new();
+        let mut links = Vec::new();
+        let mut anchors = Vec::new();
+        let mut paragraphs = Vec::new();
+        let mut current = Vec::new();
+        render_node(
+            doc.root(),
+            &mut images,
+            &mut links,
+            &mut anchors,
+            &mut paragraphs,
+            &mut current,
+            RenderAttributes::default(),
+        );
+
+        Ok(Chapter {
+            paragraphs,
+            images,
+            links,
+            anchors,
+        })
+    }
+
+    /// Resolves a link's `href` (as seen from `chapter`) to the spine index
+    /// of the file it points to, plus the `#fragment` it carries, if any.
+    pub fn resolve_link(&self, chapter: usize, href: &str) -> Option<(usize, Option<String>)> {
+        let id = &self.spine[chapter];
+        let chapter_path = Path::new(&self.manifest[id]);
+        let dir = chapter_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let (file, fragment) = href.split_once('#').unwrap_or((href, ""));
+        let path = if file.is_empty() {
+            normalize_path(chapter_path)
+        } else {
+            normalize_path(&dir.join(file))
+        };
+        let path = path.to_str()?;
+
+        let target_id = self
+            .manifest
+            .iter()
+            .find(|(_, item_path)| item_path.as_str() == path)
+            .map(|(id, _)| id)?;
+        let index = self.spine.iter().position(|i| i == target_id)?;
 
-        Ok(text)
+        Some((
+            index,
+            (!fragment.is_empty()).then(|| fragment.to_string()),
+        ))
+    }
+
+    pub fn image(&mut self, chapter: usize, src: &str) -> Result<PathBuf> {
+        let id = &self.spine[chapter];
+        let chapter_path = Path::new(&self.manifest[id]);
+        let dir = chapter_path.parent().unwrap_or_else(|| Path::new(""));
+        let path = normalize_path(&dir.join(src));
+        let path = path.to_str().ok_or_else(|| anyhow!("invalid image path"))?;
+
+        let mut archive_file = self
+            .archive
+            .by_name(path)
+            .with_context(|| path.to_string())?;
+        let mut buf = Vec::new();
+        archive_file.read_to_end(&mut buf)?;
+
+        let file_name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| anyhow!("image path has no file name"))?;
+        let out_path = std::env::temp_dir().join(file_name);
+        fs::write(&out_path, buf)?;
+
+        Ok(out_path)
+    }
+
+    /// Titles taken straight from the spine order, used when a book has no
+    /// parseable table of contents. Seeded with the book's title, if known,
+    /// since there's nothing more specific to go on.
+    fn fallback_toc(&self) -> Vec<(String, usize)> {
+        (0..self.spine.len())
+            .map(|i| {
+                let label = match &self.metadata.title {
+                    Some(title) => format!("{title} — Chapter {}", i + 1),
+                    None => format!("Chapter {}", i + 1),
+                };
+                (label, i)
+            })
+            .collect()
     }
 }
 
@@ -112,6 +247,21 @@ fn parse_package(xml: &str, base_path: &str) -> Result<Package> {
         })
         .collect();
 
+    // The table of contents is the first manifest item (in manifest order)
+    // that is either an EPUB3 navigation document (properties="nav") or an
+    // EPUB2 NCX (media-type application/x-dtbncx+xml). The spine's toc
+    // attribute is not read.
+    let toc_path = manifest_node
+        .children()
+        .filter(|n| n.has_tag_name("item"))
+        .find(|n| {
+            n.attribute("properties")
+                .map(|p| p.split_whitespace().any(|p| p == "nav"))
+                .unwrap_or(false)
+                || n.attribute("media-type") == Some("application/x-dtbncx+xml")
+        })
+        .map(|n| manifest[n.attribute("id").unwrap()].clone());
+
     let spine_node = find_node(package.root(), "package/spine")
         .ok_or_else(|| anyhow!("unable to find manifest node"))?;
     let spine: Vec<String> = spine_node
@@ -127,7 +277,122 @@ fn parse_package(xml: &str, base_path: &str) -> Result<Package> {
         .map(|n| n.attribute("idref").unwrap().to_owned())
         .collect();
 
-    Ok(Package { manifest, spine })
+    let metadata = parse_metadata(&package);
+
+    Ok(Package {
+        manifest,
+        spine,
+        toc_path,
+        metadata,
+    })
+}
+
+fn parse_metadata(package: &Document) -> Metadata {
+    let mut metadata = Metadata::default();
+
+    let Some(metadata_node) = find_node(package.root(), "package/metadata") else {
+        return metadata;
+    };
+
+    for node in metadata_node.children().filter(|n| n.is_element()) {
+        let text = || node.text().map(str::trim).filter(|s| !s.is_empty());
+
+        match node.tag_name().name() {
+            "title" if metadata.title.is_none() => metadata.title = text().map(String::from),
+            "creator" => metadata.creators.extend(text().map(String::from)),
+            "language" if metadata.language.is_none() => {
+                metadata.language = text().map(String::from)
+            }
+            "publisher" if metadata.publisher.is_none() => {
+                metadata.publisher = text().map(String::from)
+            }
+            "date" if metadata.date.is_none() => metadata.date = text().map(String::from),
+            "identifier" if metadata.identifier.is_none() => {
+                metadata.identifier = text().map(String::from)
+            }
+            // EPUB3 <meta property="..."> refinements (e.g. display-seq,
+            // file-as) aren't surfaced individually; the primary dc:
+            // elements above already cover what the reader shows.
+            _ => {}
+        }
+    }
+
+    metadata
+}
+
+fn parse_toc(
+    xml: &str,
+    toc_path: &str,
+    manifest: &HashMap<String, String>,
+    spine: &[String],
+) -> Result<Vec<(String, usize)>> {
+    let doc = Document::parse(xml)?;
+    let toc_dir = Path::new(toc_path).parent().unwrap_or_else(|| Path::new(""));
+
+    let href_to_index: HashMap<&str, usize> = manifest
+        .iter()
+        .filter_map(|(id, href)| {
+            spine
+                .iter()
+                .position(|spine_id| spine_id == id)
+                .map(|i| (href.as_str(), i))
+        })
+        .collect();
+
+    let resolve = |href: &str| -> Option<usize> {
+        let href = href.split('#').next().unwrap_or(href);
+        let path = normalize_path(&toc_dir.join(href));
+        href_to_index.get(path.to_str()?).copied()
+    };
+
+    let mut entries = Vec::new();
+
+    if let Some(nav_map) = find_node(doc.root(), "ncx/navMap") {
+        collect_nav_points(nav_map, &resolve, &mut entries);
+    } else if let Some(nav) = doc.descendants().find(|n| {
+        n.has_tag_name("nav")
+            && n.attribute(("http://www.idpf.org/2007/ops", "type")) == Some("toc")
+    }) {
+        collect_nav_list(nav, &resolve, &mut entries);
+    }
+
+    Ok(entries)
+}
+
+fn collect_nav_points(
+    node: Node,
+    resolve: &impl Fn(&str) -> Option<usize>,
+    entries: &mut Vec<(String, usize)>,
+) {
+    for nav_point in node.children().filter(|n| n.has_tag_name("navPoint")) {
+        let title = find_node(nav_point, "navLabel/text")
+            .and_then(|n| n.text())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let src = find_node(nav_point, "content").and_then(|n| n.attribute("src"));
+
+        if let (false, Some(index)) = (title.is_empty(), src.and_then(resolve)) {
+            entries.push((title, index));
+        }
+
+        collect_nav_points(nav_point, resolve, entries);
+    }
+}
+
+fn collect_nav_list(
+    node: Node,
+    resolve: &impl Fn(&str) -> Option<usize>,
+    entries: &mut Vec<(String, usize)>,
+) {
+    for a in node.descendants().filter(|n| n.has_tag_name("a")) {
+        let title = a.text().unwrap_or_default().trim().to_string();
+        let href = a.attribute("href");
+
+        if let (false, Some(index)) = (title.is_empty(), href.and_then(resolve)) {
+            entries.push((title, index));
+        }
+    }
 }
 
 fn read_archive(archive: &mut ZipArchive<File>, path: &str) -> Result<String> {
@@ -148,3 +413,18 @@ fn find_node<'a>(root: Node<'a, '_>, path: &str) -> Option<Node<'a, 'a>> {
     }
     Some(node)
 }
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        use std::path::Component::*;
+        match component {
+            CurDir => {}
+            ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
@@ -1,6 +1,26 @@
 use crossterm::style::{ContentStyle, Stylize};
 use roxmltree::{Node, NodeType};
 
+#[derive(Clone, Copy)]
+pub enum Marker {
+    Image(usize),
+    Link(usize),
+}
+
+pub struct Run {
+    pub text: String,
+    pub style: ContentStyle,
+    pub marker: Option<Marker>,
+}
+
+impl Run {
+    fn plain(text: impl Into<String>, style: ContentStyle) -> Self {
+        Run { text: text.into(), style, marker: None }
+    }
+}
+
+pub type Paragraph = Vec<Run>;
+
 #[derive(Default, Copy, Clone)]
 pub struct RenderAttributes {
     body: bool,
@@ -16,45 +36,58 @@ pub struct RenderAttributes {
 pub fn render_node(
     node: Node,
     images: &mut Vec<String>,
+    links: &mut Vec<String>,
+    anchors: &mut Vec<(String, usize)>,
+    blocks: &mut Vec<Paragraph>,
+    current: &mut Paragraph,
     mut attributes: RenderAttributes,
-) -> String {
-    let mut output = String::new();
-
-    let mut newline = false;
-    let mut linebreak = false;
+) {
+    let mut is_block = false;
 
     match node.node_type() {
-        NodeType::Element => match node.tag_name().name() {
-            "body" => attributes.body = true,
-            "p" => {
-                if !attributes.paragraph {
-                    newline = true;
-                }
-                attributes.paragraph = true;
+        NodeType::Element => {
+            if let Some(id) = node.attribute("id").or_else(|| node.attribute("name")) {
+                anchors.push((id.to_string(), blocks.len()));
             }
-            "div" => {
-                if !attributes.paragraph {
-                    newline = true;
+
+            match node.tag_name().name() {
+                "body" => attributes.body = true,
+                "p" => {
+                    if !attributes.paragraph {
+                        is_block = true;
+                    }
+                    attributes.paragraph = true;
                 }
-            }
-            "a" => attributes.link = true,
-            "b" | "strong" => attributes.bold = true,
-            "i" | "em" => attributes.italic = true,
-            "u" => attributes.underline = true,
-            "script" | "style" => attributes.nodisplay = true,
-            "br" => linebreak = true,
-            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
-                if !attributes.paragraph {
-                    newline = true;
+                "div" => {
+                    if !attributes.paragraph {
+                        is_block = true;
+                    }
                 }
-                attributes.heading = true;
-            }
-            "img" => {
-                output.push_str(&format!("[IMG:{}]", images.len()).reverse().to_string());
-                images.push(node.attribute("src").unwrap().to_string());
+                "a" => {
+                    attributes.link = true;
+                }
+                "b" | "strong" => attributes.bold = true,
+                "i" | "em" => attributes.italic = true,
+                "u" => attributes.underline = true,
+                "script" | "style" => attributes.nodisplay = true,
+                "br" => current.push(Run::plain("\n", ContentStyle::new())),
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    if !attributes.paragraph {
+                        is_block = true;
+                    }
+                    attributes.heading = true;
+                }
+                "img" => {
+                    current.push(Run {
+                        text: format!("[IMG:{}]", images.len()),
+                        style: ContentStyle::new().reverse(),
+                        marker: Some(Marker::Image(images.len())),
+                    });
+                    images.push(node.attribute("src").unwrap().to_string());
+                }
+                _ => {}
             }
-            _ => {}
-        },
+        }
         NodeType::Text => {
             if attributes.body
                 && !attributes.nodisplay
@@ -75,29 +108,66 @@ pub fn render_node(
                     style = style.underlined();
                 }
 
-                output.push_str(&style.apply(node.text().unwrap()).to_string());
+                // Text inside an <a href> carries the index its link gets
+                // once the <a>'s children are rendered, so it is clickable.
+                let marker = (attributes.link
+                    && node.ancestors().any(|n| n.has_tag_name("a") && n.has_attribute("href")))
+                .then(|| Marker::Link(links.len()));
+                current.push(Run {
+                    text: node.text().unwrap().to_string(),
+                    style,
+                    marker,
+                });
             }
         }
         _ => {}
     }
 
-    if linebreak {
-        output.push('\n');
+    for child in node.children() {
+        render_node(child, images, links, anchors, blocks, current, attributes);
     }
 
-    let mut buf = String::new();
-    for child in node.children() {
-        buf.push_str(&render_node(child, images, attributes));
+    if node.has_tag_name("a") {
+        if let Some(href) = node.attribute("href") {
+            current.push(Run {
+                text: format!("[{}]", links.len()),
+                style: ContentStyle::new().reverse(),
+                marker: Some(Marker::Link(links.len())),
+            });
+            links.push(href.to_string());
+        }
     }
 
-    if newline {
-        output.push_str(buf.trim());
-        if !buf.trim().is_empty() {
-            output.push_str("\n\n");
+    if is_block {
+        let paragraph = trim_paragraph(current);
+        if !paragraph.is_empty() {
+            blocks.push(paragraph);
         }
-    } else {
-        output.push_str(&buf);
+    }
+}
+
+fn trim_paragraph(current: &mut Paragraph) -> Paragraph {
+    let mut paragraph = std::mem::take(current);
+
+    while let Some(run) = paragraph.first() {
+        if !run.text.trim_start().is_empty() {
+            break;
+        }
+        paragraph.remove(0);
+    }
+    if let Some(run) = paragraph.first_mut() {
+        run.text = run.text.trim_start().to_string();
+    }
+
+    while let Some(run) = paragraph.last() {
+        if !run.text.trim_end().is_empty() {
+            break;
+        }
+        paragraph.pop();
+    }
+    if let Some(run) = paragraph.last_mut() {
+        run.text = run.text.trim_end().to_string();
     }
 
-    output
+    paragraph
 }
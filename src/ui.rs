@@ -1,19 +1,23 @@
-use crate::epub::Epub;
+use crate::epub::{Chapter, Epub, Marker, Paragraph};
 
 use std::io::{stdout, Write};
 
 use anyhow::Result;
 use crossterm::{
     cursor::{position, Hide, MoveLeft, MoveTo, Show},
-    event::{read, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{
+        read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        MouseButton, MouseEventKind,
+    },
     execute, queue,
-    style::{Print, Stylize},
+    style::{ContentStyle, Print, Stylize},
     terminal::{
         disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap,
-        EnterAlternateScreen, LeaveAlternateScreen,
+        EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
     },
 };
 use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct Progress {
@@ -21,6 +25,8 @@ pub struct Progress {
     pub line: usize,
 }
 
+const MAX_MEASURE: u16 = 80;
+
 pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
     let (mut current_chapter, mut current_line) = if let Some(Progress { chapter, line }) = progress
     {
@@ -30,22 +36,42 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
     };
 
     let mut status = String::new();
+    let mut search_query = String::new();
+    let mut back_stack: Vec<(usize, usize)> = Vec::new();
 
-    let (mut text, mut images) = epub.chapter(current_chapter)?;
     let mut stdout = stdout();
 
     enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, DisableLineWrap, Hide)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        DisableLineWrap,
+        EnableMouseCapture,
+        Hide
+    )?;
+    if let Some(title) = &epub.metadata.title {
+        execute!(stdout, SetTitle(format!("{title} - epsaku")))?;
+    }
 
     let (mut cols, mut rows) = size()?;
+    let mut measure = cols.min(MAX_MEASURE);
+
+    let mut chapter = epub.chapter(current_chapter)?;
+    let mut text = wrap(&chapter.paragraphs, measure.into());
 
     loop {
-        let indent = if cols > 80 { (cols - 80) / 2 } else { 0 };
+        let indent = if cols > measure { (cols - measure) / 2 } else { 0 };
 
         queue!(stdout, Clear(ClearType::All))?;
 
         for i in 0..rows {
-            if let Some(line) = text.get(usize::from(i) + current_line) {
+            if let Some(wrapped) = text.get(usize::from(i) + current_line) {
+                let line = if i == 0 && !search_query.is_empty() {
+                    highlight_match(&wrapped.text, &search_query)
+                        .unwrap_or_else(|| wrapped.text.clone())
+                } else {
+                    wrapped.text.clone()
+                };
                 queue!(stdout, MoveTo(indent, i), Print(line))?;
             }
         }
@@ -63,7 +89,15 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
             MoveTo(cols - 5, 0),
             Print(format!(" {perc:>2.0}% ").bold().reverse()),
             MoveTo(0, cols - 1),
-            Print(status.clone().bold().reverse()),
+            Print(
+                if status.is_empty() && !search_query.is_empty() {
+                    format!("/{search_query}")
+                } else {
+                    status.clone()
+                }
+                .bold()
+                .reverse()
+            ),
         )?;
 
         status.clear();
@@ -88,7 +122,8 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                         } else if current_chapter < epub.len() - 1 {
                             current_line = 0;
                             current_chapter += 1;
-                            (text, images) = epub.chapter(current_chapter)?;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
                         }
                     }
                     // Scroll up by a page
@@ -97,7 +132,8 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                             current_line -= rows as usize;
                         } else if current_line == 0 && current_chapter > 0 {
                             current_chapter -= 1;
-                            (text, images) = epub.chapter(current_chapter)?;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
                             current_line = ((text.len() - 1) / rows as usize) * rows as usize;
                         } else {
                             current_line = 0;
@@ -120,7 +156,8 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                         if current_chapter < epub.len() - 1 {
                             current_chapter += 1;
                             current_line = 0;
-                            (text, images) = epub.chapter(current_chapter)?;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
                         }
                     }
                     // Go to previous chapter
@@ -128,7 +165,8 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                         if current_chapter > 0 {
                             current_chapter -= 1;
                             current_line = 0;
-                            (text, images) = epub.chapter(current_chapter)?;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
                         }
                     }
                     // Jump to start of chapter
@@ -139,17 +177,103 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                     Char('G') => {
                         current_line = ((text.len() - 1) / rows as usize) * rows as usize;
                     }
+                    // Start a new search
+                    Char('/') => {
+                        let query = read_line("/")?;
+                        if !query.is_empty() {
+                            search_query = query;
+                            match search(
+                                epub,
+                                &search_query,
+                                current_chapter,
+                                current_line,
+                                false,
+                                measure,
+                            )? {
+                                Some((target, line)) => {
+                                    if target != current_chapter {
+                                        current_chapter = target;
+                                        chapter = epub.chapter(target)?;
+                                        text = wrap(&chapter.paragraphs, measure.into());
+                                    }
+                                    current_line = line;
+                                }
+                                None => status.push_str("No matches"),
+                            }
+                        }
+                    }
+                    // Jump to the next search match
+                    Char('n') => {
+                        if search_query.is_empty() {
+                            status.push_str("Error: no active search");
+                        } else {
+                            match search(
+                                epub,
+                                &search_query,
+                                current_chapter,
+                                current_line,
+                                false,
+                                measure,
+                            )? {
+                                Some((target, line)) => {
+                                    if target != current_chapter {
+                                        current_chapter = target;
+                                        chapter = epub.chapter(target)?;
+                                        text = wrap(&chapter.paragraphs, measure.into());
+                                    }
+                                    current_line = line;
+                                }
+                                None => status.push_str("No matches"),
+                            }
+                        }
+                    }
+                    // Jump to the previous search match
+                    Char('N') => {
+                        if search_query.is_empty() {
+                            status.push_str("Error: no active search");
+                        } else {
+                            match search(
+                                epub,
+                                &search_query,
+                                current_chapter,
+                                current_line,
+                                true,
+                                measure,
+                            )? {
+                                Some((target, line)) => {
+                                    if target != current_chapter {
+                                        current_chapter = target;
+                                        chapter = epub.chapter(target)?;
+                                        text = wrap(&chapter.paragraphs, measure.into());
+                                    }
+                                    current_line = line;
+                                }
+                                None => status.push_str("No matches"),
+                            }
+                        }
+                    }
+                    // Show book metadata
+                    Char('m') => show_metadata(epub)?,
+                    // Jump to a chapter via the table of contents
+                    Char('t') => {
+                        if let Some(index) = select_toc(epub, current_chapter)? {
+                            current_chapter = index;
+                            current_line = 0;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
+                        }
+                    }
                     Char('i') => {
-                        if images.len() == 1 {
-                            let path = epub.image(current_chapter, &images[0])?;
+                        if chapter.images.len() == 1 {
+                            let path = epub.image(current_chapter, &chapter.images[0])?;
                             std::thread::spawn(move || {
                                 open::that(&path).unwrap();
                                 let _ = std::fs::remove_file(&path);
                             });
-                        } else if !images.is_empty() {
+                        } else if !chapter.images.is_empty() {
                             let line = read_line("Image: ")?;
-                            if let Ok(sel) = line.parse::<usize>() && sel < images.len() {
-                                let path = epub.image(current_chapter, &images[sel])?;
+                            if let Ok(sel) = line.parse::<usize>() && sel < chapter.images.len() {
+                                let path = epub.image(current_chapter, &chapter.images[sel])?;
                                 std::thread::spawn(move || {
                                     open::that(&path).unwrap();
                                     let _ = std::fs::remove_file(&path);
@@ -161,16 +285,125 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
                             status.push_str("Error: no images");
                         }
                     }
+                    // Follow a hyperlink or footnote reference
+                    Char('f') => {
+                        let href = if chapter.links.len() == 1 {
+                            Some(chapter.links[0].clone())
+                        } else if !chapter.links.is_empty() {
+                            let line = read_line("Link: ")?;
+                            match line.parse::<usize>() {
+                                Ok(sel) if sel < chapter.links.len() => {
+                                    Some(chapter.links[sel].clone())
+                                }
+                                _ => {
+                                    status.push_str("Error: invalid link");
+                                    None
+                                }
+                            }
+                        } else {
+                            status.push_str("Error: no links");
+                            None
+                        };
+
+                        if let Some(href) = href {
+                            let followed = follow_link(
+                                epub,
+                                &mut back_stack,
+                                &mut current_chapter,
+                                &mut current_line,
+                                &mut chapter,
+                                &mut text,
+                                measure,
+                                &href,
+                            )?;
+                            if !followed {
+                                status.push_str("Error: broken link");
+                            }
+                        }
+                    }
+                    // Jump back to where the last link/footnote was followed from
+                    Char('b') => {
+                        if let Some((back_chapter, back_line)) = back_stack.pop() {
+                            current_chapter = back_chapter;
+                            chapter = epub.chapter(current_chapter)?;
+                            text = wrap(&chapter.paragraphs, measure.into());
+                            current_line = back_line;
+                        } else {
+                            status.push_str("Error: nothing to go back to");
+                        }
+                    }
                     _ => {}
                 }
             }
         } else if let Event::Resize(x, y) = event {
             cols = x;
             rows = y;
+
+            let new_measure = cols.min(MAX_MEASURE);
+            if new_measure != measure {
+                let paragraph = text.get(current_line).map(|line| line.paragraph).unwrap_or(0);
+                measure = new_measure;
+                text = wrap(&chapter.paragraphs, measure.into());
+                current_line = text
+                    .iter()
+                    .position(|line| line.paragraph == paragraph)
+                    .unwrap_or(0);
+            }
+        } else if let Event::Mouse(mouse) = event {
+            match mouse.kind {
+                // Scroll by a few lines, like Down/Up but faster
+                MouseEventKind::ScrollDown => {
+                    current_line = (current_line + 3).min(text.len() - 1);
+                }
+                MouseEventKind::ScrollUp => {
+                    current_line = current_line.saturating_sub(3);
+                }
+                // Click a rendered [IMG:n] marker or underlined link to
+                // trigger the same action as the keyboard path
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if mouse.column >= indent {
+                        let row = usize::from(mouse.row);
+                        let column = usize::from(mouse.column - indent);
+
+                        if let Some(wrapped) = text.get(current_line + row) {
+                            match marker_at(wrapped, column) {
+                                Some(Marker::Image(n)) => {
+                                    if let Some(src) = chapter.images.get(n).cloned() {
+                                        let path = epub.image(current_chapter, &src)?;
+                                        std::thread::spawn(move || {
+                                            open::that(&path).unwrap();
+                                            let _ = std::fs::remove_file(&path);
+                                        });
+                                    }
+                                }
+                                Some(Marker::Link(n)) => {
+                                    if let Some(href) = chapter.links.get(n).cloned() {
+                                        let followed = follow_link(
+                                            epub,
+                                            &mut back_stack,
+                                            &mut current_chapter,
+                                            &mut current_line,
+                                            &mut chapter,
+                                            &mut text,
+                                            measure,
+                                            &href,
+                                        )?;
+                                        if !followed {
+                                            status.push_str("Error: broken link");
+                                        }
+                                    }
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
     }
 
-    execute!(stdout, LeaveAlternateScreen, Show)?;
+    execute!(stdout, DisableMouseCapture, LeaveAlternateScreen, Show)?;
     disable_raw_mode()?;
 
     Ok(Progress {
@@ -179,6 +412,357 @@ pub fn run(epub: &mut Epub, progress: Option<Progress>) -> Result<Progress> {
     })
 }
 
+fn search(
+    epub: &mut Epub,
+    query: &str,
+    from_chapter: usize,
+    from_line: usize,
+    backward: bool,
+    measure: u16,
+) -> Result<Option<(usize, usize)>> {
+    let query = query.to_lowercase();
+    let chapters = epub.len();
+
+    for step in 0..=chapters {
+        let chapter = if backward {
+            (from_chapter + chapters - step % chapters) % chapters
+        } else {
+            (from_chapter + step) % chapters
+        };
+        let chapter_data = epub.chapter(chapter)?;
+        let text = wrap(&chapter_data.paragraphs, measure.into());
+
+        let matches = |line: &usize| text[*line].text.to_lowercase().contains(&query);
+
+        let found = if backward {
+            let range: Box<dyn Iterator<Item = usize>> = if step == 0 {
+                Box::new((0..from_line).rev())
+            } else if step == chapters {
+                Box::new((from_line..text.len()).rev())
+            } else {
+                Box::new((0..text.len()).rev())
+            };
+            range.filter(matches).next()
+        } else {
+            let range: Box<dyn Iterator<Item = usize>> = if step == 0 {
+                Box::new((from_line + 1)..text.len())
+            } else if step == chapters {
+                Box::new(0..(from_line + 1).min(text.len()))
+            } else {
+                Box::new(0..text.len())
+            };
+            range.filter(matches).next()
+        };
+
+        if let Some(line) = found {
+            return Ok(Some((chapter, line)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn follow_link(
+    epub: &mut Epub,
+    back_stack: &mut Vec<(usize, usize)>,
+    current_chapter: &mut usize,
+    current_line: &mut usize,
+    chapter: &mut Chapter,
+    text: &mut Vec<Line>,
+    measure: u16,
+    href: &str,
+) -> Result<bool> {
+    match epub.resolve_link(*current_chapter, href) {
+        Some((target, fragment)) => {
+            back_stack.push((*current_chapter, *current_line));
+
+            if target != *current_chapter {
+                *current_chapter = target;
+                *chapter = epub.chapter(target)?;
+                *text = wrap(&chapter.paragraphs, measure.into());
+            }
+
+            *current_line = match fragment {
+                Some(fragment) => anchor_line(&chapter.anchors, text, &fragment),
+                None => 0,
+            };
+
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn anchor_line(anchors: &[(String, usize)], text: &[Line], fragment: &str) -> usize {
+    anchors
+        .iter()
+        .find(|(id, _)| id == fragment)
+        .and_then(|&(_, paragraph)| text.iter().position(|line| line.paragraph == paragraph))
+        .unwrap_or(0)
+}
+
+fn highlight_match(line: &str, query: &str) -> Option<String> {
+    let query = query.to_lowercase();
+
+    let mut folded = String::new();
+    let mut offsets = Vec::new();
+    for (start, c) in line.char_indices() {
+        for lc in c.to_lowercase() {
+            offsets.push((folded.len(), start));
+            folded.push(lc);
+        }
+    }
+    offsets.push((folded.len(), line.len()));
+
+    let pos = folded.find(&query)?;
+    let end = pos + query.len();
+
+    let start = offsets.iter().find(|&&(f, _)| f == pos)?.1;
+    let end = offsets.iter().find(|&&(f, _)| f == end)?.1;
+
+    let mut out = String::new();
+    out.push_str(&line[..start]);
+    out.push_str(&line[start..end].reverse().to_string());
+    out.push_str(&line[end..]);
+
+    Some(out)
+}
+
+pub struct Line {
+    pub paragraph: usize,
+    pub text: String,
+    pub markers: Vec<(usize, usize, Marker)>,
+}
+
+struct Word {
+    parts: Vec<(String, ContentStyle, Option<Marker>)>,
+    width: usize,
+}
+
+enum Token {
+    Word(Word),
+    Break,
+}
+
+fn tokenize(paragraph: &Paragraph) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Word> = None;
+
+    for run in paragraph {
+        let mut fragment = String::new();
+
+        for c in run.text.chars() {
+            if c == '\n' {
+                if !fragment.is_empty() {
+                    push_fragment(&mut current, std::mem::take(&mut fragment), run.style, run.marker);
+                }
+                if let Some(word) = current.take() {
+                    tokens.push(Token::Word(word));
+                }
+                tokens.push(Token::Break);
+            } else if c.is_whitespace() {
+                if !fragment.is_empty() {
+                    push_fragment(&mut current, std::mem::take(&mut fragment), run.style, run.marker);
+                }
+                if let Some(word) = current.take() {
+                    tokens.push(Token::Word(word));
+                }
+            } else {
+                fragment.push(c);
+            }
+        }
+
+        if !fragment.is_empty() {
+            push_fragment(&mut current, fragment, run.style, run.marker);
+        }
+    }
+
+    if let Some(word) = current.take() {
+        tokens.push(Token::Word(word));
+    }
+
+    tokens
+}
+
+fn push_fragment(current: &mut Option<Word>, text: String, style: ContentStyle, marker: Option<Marker>) {
+    let width = UnicodeWidthStr::width(text.as_str());
+    match current {
+        Some(word) => {
+            word.parts.push((text, style, marker));
+            word.width += width;
+        }
+        None => *current = Some(Word { parts: vec![(text, style, marker)], width }),
+    }
+}
+
+fn render_line(paragraph: usize, runs: &[(String, ContentStyle, Option<Marker>)]) -> Line {
+    let mut text = String::new();
+    let mut markers = Vec::new();
+    let mut column = 0;
+
+    for (run_text, style, marker) in runs {
+        let width = UnicodeWidthStr::width(run_text.as_str());
+        if let Some(marker) = marker {
+            markers.push((column, width, *marker));
+        }
+        column += width;
+        text.push_str(&style.apply(run_text).to_string());
+    }
+
+    Line { paragraph, text, markers }
+}
+
+pub fn wrap(paragraphs: &[Paragraph], width: usize) -> Vec<Line> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+
+    for (index, paragraph) in paragraphs.iter().enumerate() {
+        if index > 0 {
+            lines.push(Line {
+                paragraph: index - 1,
+                text: String::new(),
+                markers: Vec::new(),
+            });
+        }
+
+        let mut line: Vec<(String, ContentStyle, Option<Marker>)> = Vec::new();
+        let mut line_width = 0;
+
+        for token in tokenize(paragraph) {
+            match token {
+                Token::Break => {
+                    lines.push(render_line(index, &line));
+                    line.clear();
+                    line_width = 0;
+                }
+                Token::Word(word) => {
+                    let space = usize::from(!line.is_empty());
+                    if !line.is_empty() && line_width + space + word.width > width {
+                        lines.push(render_line(index, &line));
+                        line.clear();
+                        line_width = 0;
+                    } else if space == 1 {
+                        line.push((" ".to_string(), ContentStyle::new(), None));
+                        line_width += 1;
+                    }
+                    line_width += word.width;
+                    line.extend(word.parts);
+                }
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(render_line(index, &line));
+        }
+    }
+
+    lines
+}
+
+fn marker_at(line: &Line, column: usize) -> Option<Marker> {
+    line.markers
+        .iter()
+        .find(|&&(start, width, _)| column >= start && column < start + width)
+        .map(|&(_, _, marker)| marker)
+}
+
+fn show_metadata(epub: &Epub) -> Result<()> {
+    let mut stdout = stdout();
+    let (_, rows) = size()?;
+
+    let fields = [
+        ("Title", epub.metadata.title.clone().unwrap_or_default()),
+        ("Author", epub.metadata.creators.join(", ")),
+        (
+            "Language",
+            epub.metadata.language.clone().unwrap_or_default(),
+        ),
+        (
+            "Publisher",
+            epub.metadata.publisher.clone().unwrap_or_default(),
+        ),
+    ];
+
+    queue!(stdout, Clear(ClearType::All))?;
+    for (i, (label, value)) in fields.iter().enumerate() {
+        queue!(
+            stdout,
+            MoveTo(0, i as u16),
+            Print(format!("{label}: ").bold()),
+            Print(value),
+        )?;
+    }
+    queue!(
+        stdout,
+        MoveTo(0, rows - 1),
+        Print("Metadata".bold().reverse()),
+    )?;
+    stdout.flush()?;
+
+    loop {
+        if let Event::Key(_) = read()? {
+            return Ok(());
+        }
+    }
+}
+
+fn select_toc(epub: &Epub, current_chapter: usize) -> Result<Option<usize>> {
+    let mut stdout = stdout();
+
+    let mut selected = epub
+        .toc
+        .iter()
+        .position(|&(_, index)| index == current_chapter)
+        .unwrap_or(0);
+
+    loop {
+        let (cols, rows) = size()?;
+
+        queue!(stdout, Clear(ClearType::All))?;
+
+        for (i, (title, index)) in epub.toc.iter().enumerate() {
+            if i >= rows as usize {
+                break;
+            }
+
+            let line = format!(" {title}");
+            let line = if *index == current_chapter {
+                line.bold()
+            } else {
+                line.stylize()
+            };
+            let line = if i == selected { line.reverse() } else { line };
+
+            queue!(stdout, MoveTo(0, i as u16), Print(line))?;
+        }
+
+        queue!(
+            stdout,
+            MoveTo(0, rows - 1),
+            Print("Contents".bold().reverse()),
+            MoveTo(cols - 1, rows - 1),
+        )?;
+
+        stdout.flush()?;
+
+        if let Event::Key(key) = read()? {
+            use crossterm::event::KeyCode::*;
+            match key.code {
+                Esc | Char('q') | Char('t') => return Ok(None),
+                Enter => return Ok(epub.toc.get(selected).map(|&(_, index)| index)),
+                Down | Char('j') => {
+                    selected = (selected + 1).min(epub.toc.len().saturating_sub(1));
+                }
+                Up | Char('k') => {
+                    selected = selected.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 pub fn read_line(prompt: &str) -> Result<String> {
     execute!(
         stdout(),